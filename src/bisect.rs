@@ -0,0 +1,148 @@
+use crate::exec;
+use crate::template::CommitContext;
+use eyre::{Result, WrapErr};
+use git2::Repository;
+use std::path::Path;
+
+/// Exit code `git bisect` (and callers who follow its convention) use to mean "this revision
+/// can't be tested, skip it".
+const SKIP_EXIT_CODE: i32 = 125;
+
+/// Binary searches `commits` (oldest to newest, as returned by `get_commits`) for the first
+/// commit where `command` starts failing.
+///
+/// `start` is assumed good and `end` (the last element of `commits`) is assumed bad; both are
+/// verified before the search begins. A commit that exits with [`SKIP_EXIT_CODE`] is treated as
+/// untestable, matching `git bisect skip`, and the search probes the next commit towards `hi`
+/// instead.
+#[tracing::instrument(skip(repo, commits, command))]
+pub fn run(
+    repo: &Repository,
+    repo_path: &Path,
+    start: git2::Oid,
+    commits: &[git2::Oid],
+    command: &str,
+) -> Result<git2::Oid> {
+    eyre::ensure!(!commits.is_empty(), "no commits in range to bisect");
+    let total = commits.len();
+
+    let start_outcome = checkout_and_run(repo, repo_path, start, command, 0, total)?;
+    eyre::ensure!(
+        start_outcome.success,
+        "start commit {} does not pass the command; bisect needs a known-good start",
+        start
+    );
+
+    let end = *commits.last().unwrap();
+    let end_outcome = checkout_and_run(repo, repo_path, end, command, commits.len() - 1, total)?;
+    eyre::ensure!(
+        !end_outcome.success,
+        "end commit {} does not fail the command; bisect needs a known-bad end",
+        end
+    );
+
+    let index = locate_first_bad(commits.len(), |mid| {
+        Ok(checkout_and_run(repo, repo_path, commits[mid], command, mid, total)?.code)
+    })?;
+
+    Ok(commits[index])
+}
+
+/// The index-only binary search at the heart of [`run`], kept free of git/process IO so it can
+/// be exercised directly with a fake `probe`.
+///
+/// `probe(index)` must return the exit code for `commits[index]`; index `len - 1` is assumed
+/// already known-bad (the caller validates this before searching). Returns the index of the
+/// first commit where `probe` starts returning a non-zero, non-skip exit code.
+fn locate_first_bad(len: usize, mut probe: impl FnMut(usize) -> Result<i32>) -> Result<usize> {
+    eyre::ensure!(len > 0, "no commits in range to bisect");
+
+    let mut lo = 0usize;
+    let mut hi = len - 1;
+
+    while lo < hi {
+        let mut mid = lo + (hi - lo) / 2;
+        let code = loop {
+            let code = probe(mid)?;
+            if code != SKIP_EXIT_CODE {
+                break code;
+            }
+            tracing::warn!(index = mid, "untestable commit, skipping");
+            mid += 1;
+            eyre::ensure!(mid < hi, "ran out of testable commits while skipping");
+        };
+
+        if code == 0 {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Ok(lo)
+}
+
+fn checkout_and_run(
+    repo: &Repository,
+    repo_path: &Path,
+    oid: git2::Oid,
+    command: &str,
+    index: usize,
+    total: usize,
+) -> Result<exec::CommitOutcome> {
+    crate::checkout(repo, oid).wrap_err_with(|| format!("checking out {}", oid))?;
+    let ctx = CommitContext::new(repo, oid, index, total)
+        .wrap_err_with(|| format!("building template context for {}", oid))?;
+    exec::run_command(repo_path, command, &ctx)
+        .wrap_err_with(|| format!("running command for {}", oid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_first_bad_commit() {
+        // indices 0,1,2 pass; 3,4 fail
+        let codes = [0, 0, 0, 1, 1];
+        let index = locate_first_bad(codes.len(), |i| Ok(codes[i])).unwrap();
+        assert_eq!(index, 3);
+    }
+
+    #[test]
+    fn only_last_commit_is_bad() {
+        let codes = [0, 0, 0, 0, 1];
+        let index = locate_first_bad(codes.len(), |i| Ok(codes[i])).unwrap();
+        assert_eq!(index, 4);
+    }
+
+    #[test]
+    fn only_first_commit_is_good() {
+        let codes = [0, 1, 1, 1, 1];
+        let index = locate_first_bad(codes.len(), |i| Ok(codes[i])).unwrap();
+        assert_eq!(index, 1);
+    }
+
+    #[test]
+    fn single_commit_range_reports_it_directly() {
+        let codes = [1];
+        let index = locate_first_bad(codes.len(), |i| Ok(codes[i])).unwrap();
+        assert_eq!(index, 0);
+    }
+
+    #[test]
+    fn skips_untestable_commits_towards_hi() {
+        // index 2 (the natural midpoint) is untestable; bisect should probe forward instead
+        // and still converge on the true first-bad index, 4.
+        let codes = [0, 0, SKIP_EXIT_CODE, 0, 1, 1];
+        let index = locate_first_bad(codes.len(), |i| Ok(codes[i])).unwrap();
+        assert_eq!(index, 4);
+    }
+
+    #[test]
+    fn errors_when_every_remaining_commit_is_untestable() {
+        let codes = [0, SKIP_EXIT_CODE, SKIP_EXIT_CODE, SKIP_EXIT_CODE, 1];
+        let err = locate_first_bad(codes.len(), |i| Ok(codes[i])).unwrap_err();
+        assert!(err.to_string().contains("ran out of testable commits"));
+    }
+}
@@ -0,0 +1,147 @@
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The name `.gcr.toml` is discovered under, alongside the repository, when `--config` isn't
+/// given explicitly.
+const DEFAULT_CONFIG_NAME: &str = ".gcr.toml";
+
+/// A single named (or default) run definition. Every field is optional so a profile can supply
+/// just the bits it wants to fix, leaving the rest to other CLI flags.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct Profile {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub command: Option<String>,
+    pub path: Option<PathBuf>,
+    pub jobs: Option<usize>,
+    pub paths: Option<Vec<String>>,
+    pub format: Option<String>,
+}
+
+/// The parsed contents of a `.gcr.toml` file: top-level keys form the default profile, and
+/// `[profile.<name>]` tables provide alternatives selectable with `--profile`.
+#[derive(Debug, Deserialize, Default)]
+pub struct ConfigFile {
+    #[serde(flatten)]
+    pub default: Profile,
+    #[serde(default)]
+    pub profile: HashMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// Returns the named profile, or the top-level default profile if `name` is `None`.
+    pub fn resolve(&self, name: Option<&str>) -> Result<Profile> {
+        match name {
+            Some(name) => self
+                .profile
+                .get(name)
+                .cloned()
+                .ok_or_else(|| eyre::eyre!("no [profile.{}] section in config file", name)),
+            None => Ok(self.default.clone()),
+        }
+    }
+}
+
+/// Loads the config file at `config_path` if given, otherwise looks for [`DEFAULT_CONFIG_NAME`]
+/// alongside `search_dir`. Returns `Ok(None)` when no `--config` was given and the default file
+/// doesn't exist; an explicit `--config` that's missing is an error.
+pub fn load(config_path: Option<&Path>, search_dir: &Path) -> Result<Option<ConfigFile>> {
+    let (path, explicit) = match config_path {
+        Some(path) => (path.to_path_buf(), true),
+        None => (search_dir.join(DEFAULT_CONFIG_NAME), false),
+    };
+
+    if !path.exists() {
+        eyre::ensure!(!explicit, "config file {:?} does not exist", path);
+        return Ok(None);
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .wrap_err_with(|| format!("reading config file {:?}", path))?;
+    let config: ConfigFile =
+        toml::from_str(&contents).wrap_err_with(|| format!("parsing config file {:?}", path))?;
+    Ok(Some(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_default_config_file_is_not_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config = load(None, dir.path()).unwrap();
+
+        assert!(config.is_none());
+    }
+
+    #[test]
+    fn explicit_missing_config_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("nope.toml");
+
+        let err = load(Some(&missing), dir.path()).unwrap_err();
+
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[test]
+    fn loads_the_default_profile_from_the_top_level_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(DEFAULT_CONFIG_NAME),
+            r#"
+            start = "main"
+            end = "HEAD"
+            command = "cargo test"
+            "#,
+        )
+        .unwrap();
+
+        let config = load(None, dir.path()).unwrap().unwrap();
+        let profile = config.resolve(None).unwrap();
+
+        assert_eq!(profile.start.as_deref(), Some("main"));
+        assert_eq!(profile.command.as_deref(), Some("cargo test"));
+    }
+
+    #[test]
+    fn loads_a_named_profile_selected_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(DEFAULT_CONFIG_NAME),
+            r#"
+            command = "default command"
+
+            [profile.release]
+            command = "release command"
+            jobs = 4
+            "#,
+        )
+        .unwrap();
+
+        let config = load(None, dir.path()).unwrap().unwrap();
+        let profile = config.resolve(Some("release")).unwrap();
+
+        assert_eq!(profile.command.as_deref(), Some("release command"));
+        assert_eq!(profile.jobs, Some(4));
+    }
+
+    #[test]
+    fn selecting_an_unknown_profile_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(DEFAULT_CONFIG_NAME),
+            r#"command = "default command""#,
+        )
+        .unwrap();
+
+        let config = load(None, dir.path()).unwrap().unwrap();
+        let err = config.resolve(Some("missing")).unwrap_err();
+
+        assert!(err.to_string().contains("no [profile.missing] section"));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::template::CommitContext;
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// How results are reported as each commit finishes.
+#[derive(Debug, Clone, Copy)]
+pub enum OutputFormat {
+    /// The original human-readable prose, printed via `println!`/`eprintln!`.
+    Text,
+    /// One JSON object per commit, written to stdout as soon as it finishes, for CI consumption.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(format!("unknown format {:?}, expected text or json", other)),
+        }
+    }
+}
+
+/// The result of running the user's command against a single commit.
+#[derive(Debug)]
+pub struct CommitOutcome {
+    pub oid: git2::Oid,
+    pub success: bool,
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u128,
+}
+
+/// The `--format json` record written for each commit, mirroring `rustc`'s `stream_cargo`
+/// JSON messages so a parent process can reliably track progress.
+#[derive(Debug, Serialize)]
+struct JsonRecord {
+    sha: String,
+    exit_code: i32,
+    success: bool,
+    stdout: String,
+    stderr: String,
+    duration_ms: u128,
+}
+
+impl From<&CommitOutcome> for JsonRecord {
+    fn from(outcome: &CommitOutcome) -> Self {
+        JsonRecord {
+            sha: outcome.oid.to_string(),
+            exit_code: outcome.code,
+            success: outcome.success,
+            stdout: outcome.stdout.clone(),
+            stderr: outcome.stderr.clone(),
+            duration_ms: outcome.duration_ms,
+        }
+    }
+}
+
+/// The `--format json` record written for a commit that `--paths` decided not to run at all.
+#[derive(Debug, Serialize)]
+struct SkippedRecord {
+    sha: String,
+    skipped: bool,
+}
+
+/// Renders `command_template`'s placeholders against `ctx`, then runs it via `bash -c` with
+/// `dir` as the working directory, also exporting `ctx`'s metadata as `GCR_*` environment
+/// variables. Captures the command's output.
+#[tracing::instrument(skip(command_template, ctx))]
+pub fn run_command(dir: &Path, command_template: &str, ctx: &CommitContext) -> Result<CommitOutcome> {
+    let command = ctx.render(command_template);
+    tracing::info!(%command, "running user specified command");
+    let start = Instant::now();
+    let output = Command::new("bash")
+        .current_dir(dir)
+        .envs(ctx.env_vars())
+        .args(["-c", &command])
+        .output()
+        .wrap_err("spawning user command")?;
+    let duration_ms = start.elapsed().as_millis();
+    let code = output.status.code().unwrap_or(1);
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    Ok(CommitOutcome {
+        oid: ctx.oid,
+        success: output.status.success(),
+        code,
+        stdout,
+        stderr,
+        duration_ms,
+    })
+}
+
+/// Reports a [`CommitOutcome`] in the requested `format`, flushing stdout immediately so
+/// consumers streaming the output see each commit as soon as it finishes.
+pub fn report(outcome: &CommitOutcome, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            if outcome.success {
+                tracing::trace!(stdout = %outcome.stdout, code = outcome.code, "successful exit code");
+                println!("Commit {:?} successful", outcome.oid);
+            } else {
+                tracing::trace!(stderr = %outcome.stderr, code = outcome.code, "failed exit code");
+                eprintln!(
+                    "Commit {:?} failed with exit code {}",
+                    outcome.oid, outcome.code
+                );
+                let stderr = outcome.stderr.trim();
+                if !stderr.is_empty() {
+                    eprintln!("{}", stderr);
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let record = JsonRecord::from(outcome);
+            let line = serde_json::to_string(&record).expect("serializing commit record");
+            println!("{}", line);
+            let _ = std::io::stdout().flush();
+        }
+    }
+}
+
+/// Reports that `oid` was skipped by `--paths` in the requested `format`, so a `--format json`
+/// consumer sees a `{"skipped": true}` record instead of a stray text line breaking the NDJSON
+/// stream.
+pub fn report_skipped(oid: git2::Oid, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            println!("Commit {:?} skipped (no changes matching --paths)", oid);
+        }
+        OutputFormat::Json => {
+            let record = SkippedRecord {
+                sha: oid.to_string(),
+                skipped: true,
+            };
+            let line = serde_json::to_string(&record).expect("serializing skipped record");
+            println!("{}", line);
+            let _ = std::io::stdout().flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_format_parses_known_values_and_rejects_others() {
+        assert!(matches!("text".parse(), Ok(OutputFormat::Text)));
+        assert!(matches!("json".parse(), Ok(OutputFormat::Json)));
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn json_record_serializes_commit_outcome_fields() {
+        let outcome = CommitOutcome {
+            oid: git2::Oid::zero(),
+            success: false,
+            code: 7,
+            stdout: "out".to_string(),
+            stderr: "err".to_string(),
+            duration_ms: 42,
+        };
+
+        let record = JsonRecord::from(&outcome);
+        let json = serde_json::to_string(&record).unwrap();
+
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"sha":"{}","exit_code":7,"success":false,"stdout":"out","stderr":"err","duration_ms":42}}"#,
+                git2::Oid::zero()
+            )
+        );
+    }
+
+    #[test]
+    fn skipped_record_serializes_as_a_flat_json_object() {
+        let record = SkippedRecord {
+            sha: git2::Oid::zero().to_string(),
+            skipped: true,
+        };
+
+        let json = serde_json::to_string(&record).unwrap();
+
+        assert_eq!(
+            json,
+            format!(r#"{{"sha":"{}","skipped":true}}"#, git2::Oid::zero())
+        );
+    }
+}
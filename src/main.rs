@@ -1,23 +1,97 @@
 use eyre::{Result, WrapErr};
 use git2::Repository;
 use std::fmt::Display;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
-#[derive(Debug, StructOpt)]
+mod bisect;
+mod config;
+mod exec;
+mod pathfilter;
+mod template;
+mod worktree;
+
+#[derive(Debug, Default, StructOpt)]
 struct Opts {
-    /// Start ref
+    /// Start ref. Falls back to the `start` key in the config file/profile
     #[structopt(short, long)]
-    start: String,
-    /// End ref
+    start: Option<String>,
+    /// End ref. Falls back to the `end` key in the config file/profile
     #[structopt(short, long)]
-    end: String,
-    /// Command to run on each commit
-    command: String,
-    /// Path to repository (defaults to current directory)
+    end: Option<String>,
+    /// Command to run on each commit. Falls back to the `command` key in the config file/profile
+    command: Option<String>,
+    /// Path to repository (defaults to current directory). Falls back to the `path` key in the
+    /// config file/profile
     #[structopt(short, long)]
     path: Option<PathBuf>,
+    /// Stash any uncommitted changes before running, restoring them afterwards, instead of
+    /// failing when the working tree is dirty
+    #[structopt(long)]
+    autostash: bool,
+    /// Run the command across commits in parallel using N detached linked worktrees, leaving
+    /// the primary working directory untouched. Falls back to the `jobs` key in the config
+    /// file/profile
+    #[structopt(short, long)]
+    jobs: Option<usize>,
+    /// Output format for per-commit results. Falls back to the `format` key in the config
+    /// file/profile, defaulting to `text`
+    #[structopt(long)]
+    format: Option<exec::OutputFormat>,
+    /// Binary search the range for the first commit where the command starts failing, instead
+    /// of running it on every commit
+    #[structopt(long)]
+    bisect: bool,
+    /// Skip commits whose diff against their parent doesn't touch any of these path globs.
+    /// Falls back to the `paths` key in the config file/profile
+    #[structopt(long)]
+    paths: Vec<String>,
+    /// Path to a `.gcr.toml` config file (defaults to discovering one next to the repository)
+    #[structopt(long)]
+    config: Option<PathBuf>,
+    /// Named `[profile.<name>]` to load from the config file, instead of its top-level defaults
+    #[structopt(long)]
+    profile: Option<String>,
+}
+
+/// Returns `true` if the working tree and index have no staged or unstaged modifications and
+/// there are no untracked files (see [`dirty_files`]).
+fn tree_is_clean(repo: &Repository) -> Result<bool> {
+    Ok(dirty_files(repo)?.is_empty())
+}
+
+/// Paths of files with staged or unstaged modifications, including untracked files. Untracked
+/// files count as dirty because `checkout`'s `force()` would silently overwrite one if a later
+/// commit in the range introduces a tracked file at the same path.
+fn dirty_files(repo: &Repository) -> Result<Vec<String>> {
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(true).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut opts)).wrap_err("reading status")?;
+    Ok(statuses
+        .iter()
+        .filter(|entry| entry.status() != git2::Status::CURRENT)
+        .filter_map(|entry| entry.path().map(str::to_string))
+        .collect())
+}
+
+/// Stashes the current working tree state, including untracked files, returning the stash's
+/// commit id. The new stash sits at index 0, where [`stash_restore`] pops it.
+fn stash_save(repo: &mut Repository) -> Result<git2::Oid> {
+    let signature = repo.signature().wrap_err("building signature")?;
+    repo.stash_save(
+        &signature,
+        "run-command-on-git-revisions autostash",
+        Some(git2::StashFlags::INCLUDE_UNTRACKED),
+    )
+    .wrap_err("stashing working tree")
+}
+
+/// Restores the most recently saved stash and drops it.
+fn stash_restore(repo: &mut Repository) -> Result<()> {
+    let mut opts = git2::StashApplyOptions::new();
+    opts.reinstantiate_index();
+    repo.stash_pop(0, Some(&mut opts))
+        .wrap_err("restoring stashed changes")
 }
 
 #[tracing::instrument(skip(repo, start, end))]
@@ -34,7 +108,7 @@ fn get_commits(
     Ok(walk.map(|oid| oid.unwrap()).collect::<Vec<_>>())
 }
 
-fn checkout(repo: &Repository, oid: git2::Oid) -> Result<()> {
+pub(crate) fn checkout(repo: &Repository, oid: git2::Oid) -> Result<()> {
     let obj = repo.revparse_single(&oid.to_string())?;
     let mut checkout_options = git2::build::CheckoutBuilder::new();
     checkout_options.force();
@@ -43,15 +117,97 @@ fn checkout(repo: &Repository, oid: git2::Oid) -> Result<()> {
     Ok(())
 }
 
-fn with_reset(repo: &Repository, f: impl FnOnce(&Repository) -> Result<()>) -> Result<()> {
+fn with_reset(
+    repo: &mut Repository,
+    autostash: bool,
+    f: impl FnOnce(&Repository) -> Result<()>,
+) -> Result<()> {
+    let stashed = if !tree_is_clean(repo)? {
+        if !autostash {
+            eyre::bail!(
+                "working tree has uncommitted changes; commit or stash them, or re-run with --autostash"
+            );
+        }
+        tracing::info!("working tree is dirty, stashing changes");
+        stash_save(repo).wrap_err("autostashing before run")?;
+        true
+    } else {
+        false
+    };
+
     // get current commit by name
-    let h = repo.head().unwrap();
-    let commit = h.peel_to_commit().wrap_err("non-commit target")?;
+    let commit_id = {
+        let h = repo.head().unwrap();
+        h.peel_to_commit().wrap_err("non-commit target")?.id()
+    };
     let res = f(repo);
-    checkout(repo, commit.id()).wrap_err_with(|| format!("checking out commit {:?}", commit))?;
+    checkout(repo, commit_id).wrap_err_with(|| format!("checking out commit {:?}", commit_id))?;
+
+    if stashed {
+        stash_restore(repo).wrap_err("restoring autostashed changes")?;
+    }
+
     res
 }
 
+/// The settings actually used for a run, after merging CLI flags over a config file profile
+/// (CLI always wins).
+struct ResolvedOpts {
+    start: String,
+    end: String,
+    command: String,
+    repo_path: PathBuf,
+    jobs: Option<usize>,
+    paths: Vec<String>,
+    format: exec::OutputFormat,
+}
+
+/// Merges `args` over `profile`, CLI flags taking priority, filling in defaults for anything
+/// neither supplies.
+fn resolve(args: Opts, profile: config::Profile) -> Result<ResolvedOpts> {
+    let start = args
+        .start
+        .or(profile.start)
+        .ok_or_else(|| eyre::eyre!("--start is required (via CLI or config file)"))?;
+    let end = args
+        .end
+        .or(profile.end)
+        .ok_or_else(|| eyre::eyre!("--end is required (via CLI or config file)"))?;
+    let command = args
+        .command
+        .or(profile.command)
+        .ok_or_else(|| eyre::eyre!("a command to run is required (via CLI or config file)"))?;
+    let repo_path = args
+        .path
+        .or(profile.path)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let jobs = args.jobs.or(profile.jobs);
+    let paths = if !args.paths.is_empty() {
+        args.paths
+    } else {
+        profile.paths.unwrap_or_default()
+    };
+    let format = match args.format {
+        Some(format) => format,
+        None => match profile.format {
+            Some(format) => format
+                .parse()
+                .map_err(|e| eyre::eyre!("invalid format in config file: {}", e))?,
+            None => exec::OutputFormat::Text,
+        },
+    };
+
+    Ok(ResolvedOpts {
+        start,
+        end,
+        command,
+        repo_path,
+        jobs,
+        paths,
+        format,
+    })
+}
+
 #[tracing::instrument]
 fn main() -> Result<()> {
     color_eyre::install().unwrap();
@@ -60,46 +216,178 @@ fn main() -> Result<()> {
     let args = Opts::from_args();
     tracing::trace!(?args, "parsed arguments");
 
-    // configure the thread pool
-    let repo_path = args.path.unwrap_or_else(|| PathBuf::from("."));
-    let repo = Repository::discover(&repo_path).wrap_err("finding repo")?;
+    // Discover the repository from the CLI-supplied path (or cwd) before looking for a config
+    // file, so `.gcr.toml` is found at the repository root even when `gcr` is invoked from a
+    // subdirectory without `--path` — the common case. This mirrors the way `Repository::discover`
+    // itself walks upward looking for `.git`.
+    let cli_path = args.path.clone().unwrap_or_else(|| PathBuf::from("."));
+    let discovery_repo = Repository::discover(&cli_path).wrap_err("finding repo")?;
+    let search_dir = discovery_repo
+        .workdir()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| discovery_repo.path().to_path_buf());
+    let profile = config::load(args.config.as_deref(), &search_dir)
+        .wrap_err("loading config file")?
+        .map(|config| config.resolve(args.profile.as_deref()))
+        .transpose()
+        .wrap_err("resolving profile")?
+        .unwrap_or_default();
+
+    let autostash = args.autostash;
+    let bisect_mode = args.bisect;
+    let ResolvedOpts {
+        start,
+        end,
+        command,
+        repo_path,
+        jobs,
+        paths,
+        format,
+    } = resolve(args, profile).wrap_err("resolving options")?;
+
+    eyre::ensure!(
+        !bisect_mode || paths.is_empty(),
+        "--bisect and --paths cannot be combined: path-filtering would drop commits from the \
+         range, including potentially --end itself, silently changing what bisect treats as \
+         the known-bad endpoint"
+    );
+    eyre::ensure!(
+        !bisect_mode || jobs.is_none(),
+        "--bisect and --jobs cannot be combined: bisect is an inherently sequential binary \
+         search, so --jobs would silently do nothing"
+    );
 
-    let commits = get_commits(&repo, &args.start, &args.end).wrap_err("computing commits")?;
+    let mut repo = Repository::discover(&repo_path).wrap_err("finding repo")?;
+
+    let commits = get_commits(&repo, &start, &end).wrap_err("computing commits")?;
     tracing::debug!(?commits, "got commits");
 
-    with_reset(&repo, |repo| {
-        for oid in commits {
-            tracing::trace!("checking out commit");
-            checkout(&repo, oid).unwrap();
-
-            let span = tracing::debug_span!("commit", sha = ?oid, command = ?args.command);
-            let _enter = span.enter();
-
-            tracing::info!("running user specified command");
-            let output = Command::new("bash")
-                .current_dir(&repo_path)
-                .args(&["-c", &args.command])
-                .output()
-                .expect("spawning user command");
-            let code = output.status.code().unwrap_or(1);
-
-            if output.status.success() {
-                let stdout = String::from_utf8(output.stdout).unwrap();
-                tracing::trace!(%stdout, %code, "successful exit code");
-                println!("Commit {:?} successful", oid);
-            } else {
-                let stderr = std::str::from_utf8(&output.stderr).unwrap();
-                tracing::trace!(%stderr, %code, "failed exit code");
-                eprintln!("Commit {:?} failed with exit code {}", oid, code);
-                let stderr = stderr.trim();
-                if !stderr.is_empty() {
-                    eprintln!("{}", stderr);
+    if bisect_mode {
+        let start_oid = repo
+            .revparse_single(&start)
+            .wrap_err("resolving start ref")?
+            .id();
+
+        return with_reset(&mut repo, autostash, |repo| {
+            let culprit = bisect::run(repo, &repo_path, start_oid, &commits, &command)
+                .wrap_err("bisecting range")?;
+            println!("First failing commit: {}", culprit);
+            Ok(())
+        })
+        .wrap_err("analysing repo");
+    }
+
+    let globs = paths
+        .iter()
+        .map(|p| glob::Pattern::new(p).wrap_err_with(|| format!("parsing path glob {:?}", p)))
+        .collect::<Result<Vec<_>>>()?;
+    let (commits, skipped) =
+        pathfilter::partition(&repo, commits, &globs).wrap_err("filtering commits by path")?;
+    for oid in skipped {
+        exec::report_skipped(oid, format);
+    }
+
+    match jobs {
+        Some(jobs) if jobs >= 1 => {
+            worktree::run_parallel(&repo_path, commits, &command, jobs, format)
+                .wrap_err("running across worktrees")?;
+        }
+        _ => {
+            let total = commits.len();
+            with_reset(&mut repo, autostash, |repo| {
+                for (index, oid) in commits.into_iter().enumerate() {
+                    tracing::trace!("checking out commit");
+                    checkout(repo, oid).unwrap();
+
+                    let span = tracing::debug_span!("commit", sha = ?oid, command = ?command);
+                    let _enter = span.enter();
+
+                    let ctx = template::CommitContext::new(repo, oid, index, total)
+                        .wrap_err_with(|| format!("building template context for {}", oid))?;
+                    let outcome = exec::run_command(&repo_path, &command, &ctx)
+                        .wrap_err_with(|| format!("running command for {}", oid))?;
+                    exec::report(&outcome, format);
                 }
-            }
+                Ok(())
+            })
+            .wrap_err("analysing repo")?;
         }
-        Ok(())
-    })
-    .wrap_err("analysing repo")?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_flags_override_profile_values() {
+        let args = Opts {
+            start: Some("cli-start".to_string()),
+            command: Some("cli command".to_string()),
+            jobs: Some(4),
+            ..Default::default()
+        };
+        let profile = config::Profile {
+            start: Some("profile-start".to_string()),
+            end: Some("profile-end".to_string()),
+            command: Some("profile command".to_string()),
+            jobs: Some(1),
+            ..Default::default()
+        };
+
+        let resolved = resolve(args, profile).unwrap();
+
+        assert_eq!(resolved.start, "cli-start");
+        assert_eq!(resolved.end, "profile-end");
+        assert_eq!(resolved.command, "cli command");
+        assert_eq!(resolved.jobs, Some(4));
+    }
+
+    #[test]
+    fn falls_back_to_profile_when_cli_omits_a_flag() {
+        let args = Opts::default();
+        let profile = config::Profile {
+            start: Some("profile-start".to_string()),
+            end: Some("profile-end".to_string()),
+            command: Some("profile command".to_string()),
+            paths: Some(vec!["src/**".to_string()]),
+            format: Some("json".to_string()),
+            ..Default::default()
+        };
+
+        let resolved = resolve(args, profile).unwrap();
+
+        assert_eq!(resolved.start, "profile-start");
+        assert_eq!(resolved.end, "profile-end");
+        assert_eq!(resolved.command, "profile command");
+        assert_eq!(resolved.paths, vec!["src/**".to_string()]);
+        assert!(matches!(resolved.format, exec::OutputFormat::Json));
+    }
+
+    #[test]
+    fn errors_when_command_is_missing_from_both_cli_and_profile() {
+        let args = Opts {
+            start: Some("s".to_string()),
+            end: Some("e".to_string()),
+            ..Default::default()
+        };
+
+        assert!(resolve(args, config::Profile::default()).is_err());
+    }
+
+    #[test]
+    fn defaults_to_text_format_when_unset_anywhere() {
+        let args = Opts {
+            start: Some("s".to_string()),
+            end: Some("e".to_string()),
+            command: Some("c".to_string()),
+            ..Default::default()
+        };
+
+        let resolved = resolve(args, config::Profile::default()).unwrap();
+
+        assert!(matches!(resolved.format, exec::OutputFormat::Text));
+    }
+}
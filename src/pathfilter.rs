@@ -0,0 +1,173 @@
+use eyre::{Result, WrapErr};
+use git2::Repository;
+
+/// Returns `true` if `oid`'s diff against its first parent touches a path matching any of
+/// `globs`. A commit with no parent (the root commit) is diffed against an empty tree, so every
+/// file it contains counts as added.
+fn touches_paths(repo: &Repository, oid: git2::Oid, globs: &[glob::Pattern]) -> Result<bool> {
+    let commit = repo.find_commit(oid).wrap_err("looking up commit")?;
+    let tree = commit.tree().wrap_err("getting commit tree")?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree().wrap_err("getting parent tree")?),
+        Err(_) => None,
+    };
+
+    let diff = repo
+        .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        .wrap_err("diffing commit against parent")?;
+
+    for delta in diff.deltas() {
+        let matches = [delta.old_file().path(), delta.new_file().path()]
+            .into_iter()
+            .flatten()
+            .any(|path| globs.iter().any(|glob| glob.matches_path(path)));
+        if matches {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Splits `commits` into those whose changes intersect `globs` and those that should be
+/// skipped. An empty `globs` list matches everything.
+pub fn partition(
+    repo: &Repository,
+    commits: Vec<git2::Oid>,
+    globs: &[glob::Pattern],
+) -> Result<(Vec<git2::Oid>, Vec<git2::Oid>)> {
+    if globs.is_empty() {
+        return Ok((commits, Vec::new()));
+    }
+
+    let mut kept = Vec::new();
+    let mut skipped = Vec::new();
+    for oid in commits {
+        if touches_paths(repo, oid, globs)? {
+            kept.push(oid);
+        } else {
+            skipped.push(oid);
+        }
+    }
+    Ok((kept, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn commit_file(repo: &Repository, path: &str, contents: &str, message: &str) -> git2::Oid {
+        let full_path = repo.workdir().unwrap().join(path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        std::fs::write(full_path, contents).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let sig = git2::Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    fn commit_rename(repo: &Repository, from: &str, to: &str, message: &str) -> git2::Oid {
+        let workdir = repo.workdir().unwrap();
+        let contents = std::fs::read(workdir.join(from)).unwrap();
+        std::fs::remove_file(workdir.join(from)).unwrap();
+        let to_path = workdir.join(to);
+        std::fs::create_dir_all(to_path.parent().unwrap()).unwrap();
+        std::fs::write(&to_path, contents).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new(from)).unwrap();
+        index.add_path(Path::new(to)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let sig = git2::Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])
+            .unwrap()
+    }
+
+    fn glob(pattern: &str) -> glob::Pattern {
+        glob::Pattern::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn root_commit_is_diffed_against_an_empty_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let root = commit_file(&repo, "src/main.rs", "fn main() {}", "initial");
+
+        // The root commit has no parent, so every file it introduces is treated as "added"
+        // against an empty tree rather than erroring for lack of a parent to diff against.
+        assert!(touches_paths(&repo, root, &[glob("src/**")]).unwrap());
+        assert!(!touches_paths(&repo, root, &[glob("docs/**")]).unwrap());
+    }
+
+    #[test]
+    fn matches_when_a_delta_touches_a_requested_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "README.md", "v1", "initial");
+        let second = commit_file(&repo, "src/main.rs", "fn main() {}", "add main");
+
+        assert!(touches_paths(&repo, second, &[glob("src/**")]).unwrap());
+    }
+
+    #[test]
+    fn does_not_match_when_no_delta_touches_a_requested_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "README.md", "v1", "initial");
+        let second = commit_file(&repo, "README.md", "v2", "update readme");
+
+        assert!(!touches_paths(&repo, second, &[glob("src/**")]).unwrap());
+    }
+
+    #[test]
+    fn a_rename_is_detected_via_either_its_old_or_new_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "src/old_name.rs", "fn main() {}", "initial");
+        let renamed = commit_rename(&repo, "src/old_name.rs", "src/new_name.rs", "rename");
+
+        assert!(touches_paths(&repo, renamed, &[glob("src/old_name.rs")]).unwrap());
+        assert!(touches_paths(&repo, renamed, &[glob("src/new_name.rs")]).unwrap());
+        assert!(!touches_paths(&repo, renamed, &[glob("docs/**")]).unwrap());
+    }
+
+    #[test]
+    fn partition_with_no_globs_keeps_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let first = commit_file(&repo, "README.md", "v1", "initial");
+
+        let (kept, skipped) = partition(&repo, vec![first], &[]).unwrap();
+
+        assert_eq!(kept, vec![first]);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn partition_splits_commits_by_whether_they_touch_the_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let first = commit_file(&repo, "src/main.rs", "fn main() {}", "initial");
+        let second = commit_file(&repo, "README.md", "docs", "docs only");
+
+        let (kept, skipped) = partition(
+            &repo,
+            vec![first, second],
+            &[glob("src/**")],
+        )
+        .unwrap();
+
+        assert_eq!(kept, vec![first]);
+        assert_eq!(skipped, vec![second]);
+    }
+}
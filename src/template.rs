@@ -0,0 +1,181 @@
+use eyre::{Result, WrapErr};
+use git2::Repository;
+
+/// Per-commit metadata available to the user's command, both as `{placeholder}` interpolation
+/// in the command string and as `GCR_*` environment variables on the spawned process.
+///
+/// `subject` and `author` come straight from commit metadata, which is attacker-controlled in
+/// any repo whose history isn't fully trusted (anyone who can push a commit chooses their own
+/// author and message). Since `render`'s output is handed to `bash -c` verbatim, a `{subject}`/
+/// `{author}` placeholder in the command template is a command-injection vector against
+/// untrusted history — don't template on those fields unless every commit author is trusted.
+pub struct CommitContext {
+    pub oid: git2::Oid,
+    pub sha: String,
+    pub short_sha: String,
+    pub subject: String,
+    pub author: String,
+    pub index: usize,
+    pub total: usize,
+}
+
+impl CommitContext {
+    pub fn new(repo: &Repository, oid: git2::Oid, index: usize, total: usize) -> Result<Self> {
+        let commit = repo.find_commit(oid).wrap_err("looking up commit")?;
+        let short_sha = commit
+            .as_object()
+            .short_id()
+            .wrap_err("computing short sha")?
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let author = commit.author();
+        let author = format!(
+            "{} <{}>",
+            author.name().unwrap_or_default(),
+            author.email().unwrap_or_default()
+        );
+
+        Ok(CommitContext {
+            oid,
+            sha: oid.to_string(),
+            short_sha,
+            subject: commit.summary().unwrap_or_default().to_string(),
+            author,
+            index,
+            total,
+        })
+    }
+
+    /// Replaces `{sha}`, `{short_sha}`, `{subject}`, `{author}`, `{index}` and `{total}`
+    /// placeholders in `command` with this commit's metadata, in a single left-to-right pass.
+    ///
+    /// Unrecognised `{...}` tokens are left untouched. A single pass is essential here, not just
+    /// a style choice: chained `.replace()` calls re-scan the *entire* string on each step, so a
+    /// placeholder token that happens to occur inside an *already-substituted* value (e.g. a
+    /// commit subject containing the literal text `{author}`) would get replaced again by a
+    /// later call, silently corrupting the rendered command.
+    pub fn render(&self, command: &str) -> String {
+        let mut rendered = String::with_capacity(command.len());
+        let mut rest = command;
+
+        while let Some(open) = rest.find('{') {
+            rendered.push_str(&rest[..open]);
+            match rest[open..].find('}') {
+                Some(close_rel) => {
+                    let close = open + close_rel;
+                    let token = &rest[open + 1..close];
+                    match self.placeholder(token) {
+                        Some(value) => rendered.push_str(&value),
+                        None => rendered.push_str(&rest[open..=close]),
+                    }
+                    rest = &rest[close + 1..];
+                }
+                None => {
+                    rendered.push_str(&rest[open..]);
+                    rest = "";
+                    break;
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+
+    /// Looks up a single `{token}` against this commit's metadata, returning `None` for anything
+    /// that isn't a recognised placeholder (left as literal text by [`Self::render`]).
+    fn placeholder(&self, token: &str) -> Option<String> {
+        match token {
+            "sha" => Some(self.sha.clone()),
+            "short_sha" => Some(self.short_sha.clone()),
+            "subject" => Some(self.subject.clone()),
+            "author" => Some(self.author.clone()),
+            "index" => Some(self.index.to_string()),
+            "total" => Some(self.total.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The `GCR_SHA`, `GCR_SHORT_SHA`, `GCR_SUBJECT`, `GCR_AUTHOR`, `GCR_INDEX` and `GCR_TOTAL`
+    /// environment variables to export on the spawned command.
+    pub fn env_vars(&self) -> [(&'static str, String); 6] {
+        [
+            ("GCR_SHA", self.sha.clone()),
+            ("GCR_SHORT_SHA", self.short_sha.clone()),
+            ("GCR_SUBJECT", self.subject.clone()),
+            ("GCR_AUTHOR", self.author.clone()),
+            ("GCR_INDEX", self.index.to_string()),
+            ("GCR_TOTAL", self.total.to_string()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> CommitContext {
+        CommitContext {
+            oid: git2::Oid::zero(),
+            sha: "abc123def456".to_string(),
+            short_sha: "abc123d".to_string(),
+            subject: "Fix the thing".to_string(),
+            author: "Alice <alice@example.com>".to_string(),
+            index: 2,
+            total: 5,
+        }
+    }
+
+    #[test]
+    fn replaces_all_placeholders() {
+        let ctx = ctx();
+        let rendered = ctx.render("{sha} {short_sha} {subject} {author} {index}/{total}");
+        assert_eq!(
+            rendered,
+            "abc123def456 abc123d Fix the thing Alice <alice@example.com> 2/5"
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_braces_untouched() {
+        let ctx = ctx();
+        assert_eq!(ctx.render("echo {nope}"), "echo {nope}");
+    }
+
+    #[test]
+    fn unterminated_brace_is_left_as_literal_text() {
+        let ctx = ctx();
+        assert_eq!(ctx.render("echo {sha"), "echo {sha");
+    }
+
+    #[test]
+    fn does_not_re_substitute_a_placeholder_token_embedded_in_another_fields_value() {
+        // A subject containing the literal text "{author}" must not have that text replaced by
+        // a later substitution pass — it's part of the subject's rendered value, not a
+        // placeholder in the original template.
+        let mut ctx = ctx();
+        ctx.subject = "Bump {author} version".to_string();
+
+        let rendered = ctx.render(r#"echo "{subject}""#);
+
+        assert_eq!(rendered, r#"echo "Bump {author} version""#);
+    }
+
+    #[test]
+    fn env_vars_cover_all_six_fields() {
+        let ctx = ctx();
+        let vars = ctx.env_vars();
+        assert_eq!(
+            vars,
+            [
+                ("GCR_SHA", "abc123def456".to_string()),
+                ("GCR_SHORT_SHA", "abc123d".to_string()),
+                ("GCR_SUBJECT", "Fix the thing".to_string()),
+                ("GCR_AUTHOR", "Alice <alice@example.com>".to_string()),
+                ("GCR_INDEX", "2".to_string()),
+                ("GCR_TOTAL", "5".to_string()),
+            ]
+        );
+    }
+}
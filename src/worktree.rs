@@ -0,0 +1,75 @@
+use crate::exec::{self, CommitOutcome, OutputFormat};
+use crate::template::CommitContext;
+use eyre::{Result, WrapErr};
+use git2::Repository;
+use std::path::Path;
+use std::process::Command;
+
+/// Adds a detached linked worktree for `oid` at `dest`.
+fn add(repo_path: &Path, dest: &Path, oid: git2::Oid) -> Result<()> {
+    let status = Command::new("git")
+        .current_dir(repo_path)
+        .args(["worktree", "add", "--detach"])
+        .arg(dest)
+        .arg(oid.to_string())
+        .status()
+        .wrap_err("spawning git worktree add")?;
+    eyre::ensure!(status.success(), "git worktree add failed for {}", oid);
+    Ok(())
+}
+
+/// Removes a linked worktree created by [`add`], discarding any changes left behind by the
+/// user's command.
+fn remove(repo_path: &Path, dest: &Path) -> Result<()> {
+    let status = Command::new("git")
+        .current_dir(repo_path)
+        .args(["worktree", "remove", "--force"])
+        .arg(dest)
+        .status()
+        .wrap_err("spawning git worktree remove")?;
+    eyre::ensure!(status.success(), "git worktree remove failed for {:?}", dest);
+    Ok(())
+}
+
+/// Runs `command` against every commit in `commits`, each in its own detached worktree, using
+/// up to `jobs` worktrees (and threads) at a time. The primary working directory is never
+/// touched.
+#[tracing::instrument(skip(commits, command))]
+pub fn run_parallel(
+    repo_path: &Path,
+    commits: Vec<git2::Oid>,
+    command: &str,
+    jobs: usize,
+    format: OutputFormat,
+) -> Result<Vec<CommitOutcome>> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .wrap_err("configuring the thread pool")?;
+
+    let base = tempfile::tempdir().wrap_err("creating worktree scratch directory")?;
+    let total = commits.len();
+
+    pool.install(|| {
+        use rayon::prelude::*;
+        commits
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, oid)| {
+                let repo = Repository::discover(repo_path).wrap_err("opening repo in worker")?;
+                let ctx = CommitContext::new(&repo, oid, index, total)
+                    .wrap_err_with(|| format!("building template context for {}", oid))?;
+
+                let dest = base.path().join(oid.to_string());
+                add(repo_path, &dest, oid)
+                    .wrap_err_with(|| format!("adding worktree for {}", oid))?;
+                let result = exec::run_command(&dest, command, &ctx);
+                remove(repo_path, &dest)
+                    .wrap_err_with(|| format!("removing worktree for {}", oid))?;
+                let outcome = result?;
+                exec::report(&outcome, format);
+                Ok(outcome)
+            })
+            .collect()
+    })
+}